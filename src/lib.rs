@@ -1,39 +1,554 @@
+use jsonschema::JSONSchema;
 use schemars::schema::RootSchema;
 use schemars::{schema_for, JsonSchema};
 use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::error::Error;
 use std::fmt::{Display, Formatter};
+use std::future::Future;
 use std::marker::PhantomData;
+use std::pin::Pin;
 
 pub trait ModelClient<Deps> {
     fn complete(&self, prompt: &str, deps: &Deps) -> Result<String, Box<dyn Error + Send + Sync>>;
 }
 
+#[derive(Debug, Clone)]
+pub struct ValidationError {
+    pub msg: String,
+    pub instance_path: Vec<String>,
+    pub schema_path: Vec<String>,
+}
+
+impl ValidationError {
+    fn new(msg: impl Into<String>) -> Self {
+        Self {
+            msg: msg.into(),
+            instance_path: Vec::new(),
+            schema_path: Vec::new(),
+        }
+    }
+
+    fn instance_pointer(&self) -> String {
+        join_pointer(&self.instance_path)
+    }
+
+    fn schema_pointer(&self) -> String {
+        join_pointer(&self.schema_path)
+    }
+}
+
+impl Display for ValidationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} (schema {}): {}",
+            self.instance_pointer(),
+            self.schema_pointer(),
+            self.msg
+        )
+    }
+}
+
+impl Error for ValidationError {}
+
+fn join_pointer(segments: &[String]) -> String {
+    if segments.is_empty() {
+        "/".to_string()
+    } else {
+        format!("/{}", segments.join("/"))
+    }
+}
+
+fn parse_response(response: &str, lenient: bool) -> serde_json::Result<serde_json::Value> {
+    match serde_json::from_str(response) {
+        Ok(value) => Ok(value),
+        Err(err) => {
+            if lenient {
+                if let Some(repaired) = repair_json(response) {
+                    if let Ok(value) = serde_json::from_str(&repaired) {
+                        return Ok(value);
+                    }
+                }
+            }
+            Err(err)
+        }
+    }
+}
+
+fn repair_json(raw: &str) -> Option<String> {
+    let sanitized = sanitize_lone_surrogates(raw);
+    extract_balanced_span(&sanitized)
+}
+
+fn extract_balanced_span(input: &str) -> Option<String> {
+    let start = input.find(['{', '['])?;
+    let open = input[start..].chars().next()?;
+    let close = if open == '{' { '}' } else { ']' };
+
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escape = false;
+
+    for (offset, ch) in input[start..].char_indices() {
+        if in_string {
+            if escape {
+                escape = false;
+            } else if ch == '\\' {
+                escape = true;
+            } else if ch == '"' {
+                in_string = false;
+            }
+            continue;
+        }
+
+        if ch == '"' {
+            in_string = true;
+        } else if ch == open {
+            depth += 1;
+        } else if ch == close {
+            depth -= 1;
+            if depth == 0 {
+                let end = start + offset + ch.len_utf8();
+                return Some(input[start..end].to_string());
+            }
+        }
+    }
+
+    None
+}
+
+fn sanitize_lone_surrogates(raw: &str) -> String {
+    let mut out = String::with_capacity(raw.len());
+    let mut rest = raw;
+    let mut in_string = false;
+
+    while !rest.is_empty() {
+        if !in_string {
+            let ch = rest.chars().next().expect("rest is non-empty");
+            out.push(ch);
+            rest = &rest[ch.len_utf8()..];
+            if ch == '"' {
+                in_string = true;
+            }
+            continue;
+        }
+
+        if rest.starts_with('"') {
+            out.push('"');
+            rest = &rest[1..];
+            in_string = false;
+            continue;
+        }
+
+        if rest.starts_with('\\') {
+            // Every backslash inside a JSON string starts a new escape sequence, so
+            // scanning one escape at a time (rather than pattern-matching `\u` anywhere
+            // in the text) keeps an escaped backslash (`\\`) from being misread as the
+            // start of a `\u` escape when it's immediately followed by a literal `u`.
+            if let Some(code) = rest.get(0..6).and_then(parse_unicode_escape) {
+                if (0xD800..=0xDBFF).contains(&code) {
+                    if let Some(low) = rest.get(6..12).and_then(parse_unicode_escape) {
+                        if (0xDC00..=0xDFFF).contains(&low) {
+                            out.push_str(&rest[..12]);
+                            rest = &rest[12..];
+                            continue;
+                        }
+                    }
+                    out.push('\u{FFFD}');
+                    rest = &rest[6..];
+                    continue;
+                } else if (0xDC00..=0xDFFF).contains(&code) {
+                    out.push('\u{FFFD}');
+                    rest = &rest[6..];
+                    continue;
+                }
+            }
+
+            let escaped_len = 1 + rest[1..].chars().next().map_or(0, char::len_utf8);
+            out.push_str(&rest[..escaped_len]);
+            rest = &rest[escaped_len..];
+            continue;
+        }
+
+        let ch = rest.chars().next().expect("rest is non-empty");
+        out.push(ch);
+        rest = &rest[ch.len_utf8()..];
+    }
+
+    out
+}
+
+fn parse_unicode_escape(s: &str) -> Option<u32> {
+    let hex = s.strip_prefix("\\u")?;
+    u32::from_str_radix(hex, 16).ok()
+}
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+fn block_on_ready<F: Future>(future: F) -> F::Output {
+    use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+    fn noop(_: *const ()) {}
+    fn clone(_: *const ()) -> RawWaker {
+        RawWaker::new(std::ptr::null(), &VTABLE)
+    }
+    static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+
+    let waker = unsafe { Waker::from_raw(RawWaker::new(std::ptr::null(), &VTABLE)) };
+    let mut cx = Context::from_waker(&waker);
+    let mut future = Box::pin(future);
+
+    match future.as_mut().poll(&mut cx) {
+        Poll::Ready(output) => output,
+        Poll::Pending => unreachable!("Agent::run only drives futures that resolve eagerly"),
+    }
+}
+
+async fn run_loop<'d, Deps, Output>(
+    prompt: &str,
+    tools: &ToolRegistry,
+    max_retries: usize,
+    max_tool_steps: usize,
+    lenient_parsing: bool,
+    deps: &'d Deps,
+    complete: impl Fn(String, &'d Deps) -> BoxFuture<'d, Result<String, Box<dyn Error + Send + Sync>>>,
+) -> Result<Output, AgentError>
+where
+    Output: DeserializeOwned + JsonSchema,
+{
+    let base_prompt = format!("{prompt}{}", tools.system_prompt_section());
+    // Tracks the conversation so far (base prompt plus any tool results), without the
+    // ephemeral reflection note appended for the current retry attempt. Rebuilding
+    // reflection prompts from this instead of `base_prompt` keeps tool results in scope
+    // across validation retries.
+    let mut conversation_prompt = base_prompt.clone();
+    let mut current_prompt = conversation_prompt.clone();
+    let mut trace: Vec<AttemptRecord> = Vec::new();
+    let mut attempt = 0;
+    let mut tool_steps = 0;
+
+    loop {
+        let response = match complete(current_prompt.clone(), deps).await {
+            Ok(response) => response,
+            Err(err) => {
+                trace.push(AttemptRecord {
+                    prompt: current_prompt,
+                    response: None,
+                    failure: AttemptFailure::Transport(err),
+                });
+                return Err(AgentError::Model { attempts: trace });
+            }
+        };
+
+        let value: serde_json::Value = match parse_response(&response, lenient_parsing) {
+            Ok(value) => value,
+            Err(err) => {
+                trace.push(AttemptRecord {
+                    prompt: current_prompt,
+                    response: Some(response),
+                    failure: AttemptFailure::Parse(Box::new(err)),
+                });
+
+                if attempt == max_retries {
+                    return Err(AgentError::Validation { attempts: trace });
+                }
+
+                attempt += 1;
+                current_prompt = format!(
+                    "{conversation_prompt}\n\nPrevious response did not match schema.\nValidation errors:\n{}\nReturn valid JSON only.",
+                    trace.last().expect("just pushed").failure
+                );
+                continue;
+            }
+        };
+
+        let tool_call = ToolCall::from_value(&value).filter(|call| tools.contains(&call.method));
+        if let Some(call) = tool_call {
+            if tool_steps == max_tool_steps {
+                return Err(AgentError::ToolStepsExceeded { max_steps: max_tool_steps });
+            }
+            tool_steps += 1;
+
+            let result = tools.dispatch(&call)?;
+            conversation_prompt = format!(
+                "{conversation_prompt}\n\nTool `{}` returned:\n{result}\n\nContinue, or return the final answer as JSON.",
+                call.method
+            );
+            current_prompt = conversation_prompt.clone();
+            continue;
+        }
+
+        match validate_output::<Output>(value) {
+            Ok(output) => return Ok(output),
+            Err(violations) => {
+                trace.push(AttemptRecord {
+                    prompt: current_prompt,
+                    response: Some(response),
+                    failure: AttemptFailure::Schema(violations),
+                });
+
+                if attempt == max_retries {
+                    return Err(AgentError::Validation { attempts: trace });
+                }
+
+                attempt += 1;
+                current_prompt = format!(
+                    "{conversation_prompt}\n\nPrevious response did not match schema.\nValidation errors:\n{}\nReturn valid JSON only.",
+                    trace.last().expect("just pushed").failure
+                );
+            }
+        }
+    }
+}
+
+fn validate_output<Output: DeserializeOwned + JsonSchema>(
+    value: serde_json::Value,
+) -> Result<Output, Vec<ValidationError>> {
+    let violations = validate_against_schema::<Output>(&value);
+    if !violations.is_empty() {
+        return Err(violations);
+    }
+
+    serde_json::from_value(value).map_err(|err| vec![ValidationError::new(err.to_string())])
+}
+
+fn validate_against_schema<Output: JsonSchema>(value: &serde_json::Value) -> Vec<ValidationError> {
+    let schema = schema_for!(Output);
+    let schema_value = serde_json::to_value(&schema).expect("generated schema serializes to JSON");
+    let compiled = JSONSchema::compile(&schema_value).expect("generated schema compiles");
+
+    let violations = match compiled.validate(value) {
+        Ok(()) => Vec::new(),
+        Err(errors) => errors
+            .map(|err| ValidationError {
+                msg: err.to_string(),
+                instance_path: err.instance_path.into_vec(),
+                schema_path: err.schema_path.into_vec(),
+            })
+            .collect(),
+    };
+    violations
+}
+
+#[derive(Debug)]
+pub enum AttemptFailure {
+    Transport(Box<dyn Error + Send + Sync>),
+    Parse(Box<dyn Error + Send + Sync>),
+    Schema(Vec<ValidationError>),
+}
+
+impl Display for AttemptFailure {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Transport(err) => write!(f, "transport error: {err}"),
+            Self::Parse(err) => write!(f, "parse error: {err}"),
+            Self::Schema(violations) => {
+                for (i, violation) in violations.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "; ")?;
+                    }
+                    write!(
+                        f,
+                        "field `{}` failed constraint at schema `{}`: {}",
+                        violation.instance_pointer(),
+                        violation.schema_pointer(),
+                        violation.msg
+                    )?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl AttemptFailure {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Transport(err) => Some(err.as_ref()),
+            Self::Parse(err) => Some(err.as_ref()),
+            Self::Schema(violations) => violations.first().map(|v| v as &(dyn Error + 'static)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct AttemptRecord {
+    pub prompt: String,
+    pub response: Option<String>,
+    pub failure: AttemptFailure,
+}
+
 #[derive(Debug)]
 pub enum AgentError {
-    Model(String),
+    Model {
+        attempts: Vec<AttemptRecord>,
+    },
     Validation {
-        attempts: usize,
+        attempts: Vec<AttemptRecord>,
+    },
+    ToolMethodNotFound(String),
+    ToolInvalidParams {
+        method: String,
         message: String,
     },
+    ToolStepsExceeded {
+        max_steps: usize,
+    },
+}
+
+impl AgentError {
+    pub fn attempts(&self) -> &[AttemptRecord] {
+        match self {
+            Self::Model { attempts } | Self::Validation { attempts } => attempts,
+            Self::ToolMethodNotFound(_) | Self::ToolInvalidParams { .. } | Self::ToolStepsExceeded { .. } => &[],
+        }
+    }
 }
 
 impl Display for AgentError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::Model(message) => write!(f, "model error: {message}"),
-            Self::Validation { attempts, message } => {
-                write!(f, "validation failed after {attempts} attempts: {message}")
+            Self::Model { attempts } => write!(
+                f,
+                "model error after {} attempt(s): {}",
+                attempts.len(),
+                attempts.last().map(|a| a.failure.to_string()).unwrap_or_default()
+            ),
+            Self::Validation { attempts } => write!(
+                f,
+                "validation failed after {} attempt(s): {}",
+                attempts.len(),
+                attempts.last().map(|a| a.failure.to_string()).unwrap_or_default()
+            ),
+            Self::ToolMethodNotFound(method) => write!(f, "tool call error: method not found: {method}"),
+            Self::ToolInvalidParams { method, message } => {
+                write!(f, "tool call error: invalid params for `{method}`: {message}")
+            }
+            Self::ToolStepsExceeded { max_steps } => {
+                write!(f, "tool call error: exceeded max tool steps ({max_steps})")
+            }
+        }
+    }
+}
+
+impl Error for AgentError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Model { attempts } | Self::Validation { attempts } => {
+                attempts.last().and_then(|record| record.failure.source())
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum ToolCallError {
+    MethodNotFound(String),
+    InvalidParams { method: String, message: String },
+}
+
+impl From<ToolCallError> for AgentError {
+    fn from(err: ToolCallError) -> Self {
+        match err {
+            ToolCallError::MethodNotFound(method) => AgentError::ToolMethodNotFound(method),
+            ToolCallError::InvalidParams { method, message } => {
+                AgentError::ToolInvalidParams { method, message }
             }
         }
     }
 }
 
-impl Error for AgentError {}
+#[derive(Debug, Clone, Deserialize)]
+struct ToolCall {
+    method: String,
+    #[serde(default)]
+    params: serde_json::Value,
+}
+
+impl ToolCall {
+    fn from_value(value: &serde_json::Value) -> Option<Self> {
+        value.get("method")?.as_str()?;
+        serde_json::from_value(value.clone()).ok()
+    }
+}
+
+trait ErasedTool {
+    fn name(&self) -> &'static str;
+    fn input_schema(&self) -> RootSchema;
+    fn dispatch(&self, params: serde_json::Value) -> Result<serde_json::Value, String>;
+}
+
+impl<T: Tool> ErasedTool for T {
+    fn name(&self) -> &'static str {
+        Tool::name(self)
+    }
+
+    fn input_schema(&self) -> RootSchema {
+        T::input_schema()
+    }
+
+    fn dispatch(&self, params: serde_json::Value) -> Result<serde_json::Value, String> {
+        let input: T::Input = serde_json::from_value(params).map_err(|err| err.to_string())?;
+        let output = self.call(input);
+        serde_json::to_value(output).map_err(|err| err.to_string())
+    }
+}
+
+#[derive(Default)]
+pub struct ToolRegistry {
+    tools: HashMap<&'static str, Box<dyn ErasedTool>>,
+}
+
+impl ToolRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register<T: Tool + 'static>(mut self, tool: T) -> Self {
+        self.tools.insert(tool.name(), Box::new(tool));
+        self
+    }
+
+    fn contains(&self, method: &str) -> bool {
+        self.tools.contains_key(method)
+    }
+
+    fn dispatch(&self, call: &ToolCall) -> Result<serde_json::Value, ToolCallError> {
+        let tool = self
+            .tools
+            .get(call.method.as_str())
+            .ok_or_else(|| ToolCallError::MethodNotFound(call.method.clone()))?;
+
+        tool.dispatch(call.params.clone())
+            .map_err(|message| ToolCallError::InvalidParams {
+                method: call.method.clone(),
+                message,
+            })
+    }
+
+    fn system_prompt_section(&self) -> String {
+        if self.tools.is_empty() {
+            return String::new();
+        }
+
+        let mut section = String::from("\n\nAvailable tools (call one via {\"method\": <name>, \"params\": <input>}):\n");
+        for tool in self.tools.values() {
+            let schema = serde_json::to_value(tool.input_schema()).unwrap_or(serde_json::Value::Null);
+            section.push_str(&format!("- {}: input schema {schema}\n", tool.name()));
+        }
+        section
+    }
+}
 
 pub struct Agent<Deps, Output, Model> {
     model: Model,
     max_retries: usize,
+    max_tool_steps: usize,
+    tools: ToolRegistry,
+    lenient_parsing: bool,
     _marker: PhantomData<(Deps, Output)>,
 }
 
@@ -46,6 +561,9 @@ where
         Self {
             model,
             max_retries: 2,
+            max_tool_steps: 5,
+            tools: ToolRegistry::new(),
+            lenient_parsing: false,
             _marker: PhantomData,
         }
     }
@@ -55,47 +573,44 @@ where
         self
     }
 
-    pub fn output_schema() -> RootSchema {
-        schema_for!(Output)
+    pub fn with_tools(mut self, tools: ToolRegistry) -> Self {
+        self.tools = tools;
+        self
     }
 
-    pub fn run(&self, prompt: &str, deps: &Deps) -> Result<Output, AgentError> {
-        let mut current_prompt = prompt.to_owned();
-        let mut last_validation_error = None;
-
-        for attempt in 0..=self.max_retries {
-            let response = self
-                .model
-                .complete(&current_prompt, deps)
-                .map_err(|err| AgentError::Model(err.to_string()))?;
-
-            match serde_json::from_str::<Output>(&response) {
-                Ok(output) => return Ok(output),
-                Err(err) => {
-                    last_validation_error = Some(err.to_string());
+    pub fn with_max_tool_steps(mut self, max_tool_steps: usize) -> Self {
+        self.max_tool_steps = max_tool_steps;
+        self
+    }
 
-                    if attempt == self.max_retries {
-                        break;
-                    }
+    pub fn with_lenient_parsing(mut self, lenient_parsing: bool) -> Self {
+        self.lenient_parsing = lenient_parsing;
+        self
+    }
 
-                    current_prompt = format!(
-                        "{prompt}\n\nPrevious response did not match schema.\nValidation error: {err}\nReturn valid JSON only."
-                    );
-                }
-            }
-        }
+    pub fn output_schema() -> RootSchema {
+        schema_for!(Output)
+    }
 
-        Err(AgentError::Validation {
-            attempts: self.max_retries + 1,
-            message: last_validation_error
-                .unwrap_or_else(|| "unknown validation error".to_string()),
-        })
+    pub fn run(&self, prompt: &str, deps: &Deps) -> Result<Output, AgentError> {
+        block_on_ready(run_loop(
+            prompt,
+            &self.tools,
+            self.max_retries,
+            self.max_tool_steps,
+            self.lenient_parsing,
+            deps,
+            |prompt, deps| {
+                let result = self.model.complete(&prompt, deps);
+                Box::pin(std::future::ready(result)) as BoxFuture<'_, _>
+            },
+        ))
     }
 }
 
 pub trait Tool {
     type Input: DeserializeOwned + JsonSchema;
-    type Output;
+    type Output: Serialize;
 
     fn name(&self) -> &'static str;
     fn call(&self, input: Self::Input) -> Self::Output;
@@ -105,17 +620,123 @@ pub trait Tool {
     }
 }
 
+#[cfg(feature = "async")]
+pub trait AsyncModelClient<Deps>: Send + Sync {
+    fn complete<'a>(
+        &'a self,
+        prompt: String,
+        deps: &'a Deps,
+    ) -> BoxFuture<'a, Result<String, Box<dyn Error + Send + Sync>>>;
+}
+
+#[cfg(feature = "async")]
+pub struct AsyncAgent<Deps, Output, Model> {
+    model: Model,
+    max_retries: usize,
+    max_tool_steps: usize,
+    tools: ToolRegistry,
+    lenient_parsing: bool,
+    _marker: PhantomData<(Deps, Output)>,
+}
+
+#[cfg(feature = "async")]
+impl<Deps, Output, Model> AsyncAgent<Deps, Output, Model>
+where
+    Deps: Sync,
+    Output: DeserializeOwned + JsonSchema,
+    Model: AsyncModelClient<Deps>,
+{
+    pub fn new(model: Model) -> Self {
+        Self {
+            model,
+            max_retries: 2,
+            max_tool_steps: 5,
+            tools: ToolRegistry::new(),
+            lenient_parsing: false,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn with_max_retries(mut self, max_retries: usize) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    pub fn with_tools(mut self, tools: ToolRegistry) -> Self {
+        self.tools = tools;
+        self
+    }
+
+    pub fn with_max_tool_steps(mut self, max_tool_steps: usize) -> Self {
+        self.max_tool_steps = max_tool_steps;
+        self
+    }
+
+    pub fn with_lenient_parsing(mut self, lenient_parsing: bool) -> Self {
+        self.lenient_parsing = lenient_parsing;
+        self
+    }
+
+    pub fn output_schema() -> RootSchema {
+        schema_for!(Output)
+    }
+
+    pub async fn run(&self, prompt: &str, deps: &Deps) -> Result<Output, AgentError> {
+        run_loop(
+            prompt,
+            &self.tools,
+            self.max_retries,
+            self.max_tool_steps,
+            self.lenient_parsing,
+            deps,
+            |prompt, deps| self.model.complete(prompt, deps),
+        )
+        .await
+    }
+}
+
 #[cfg(test)]
-mod tests {
+mod test_support {
     use super::*;
     use serde::Deserialize;
-    use std::cell::RefCell;
 
     #[derive(Debug, Deserialize, JsonSchema, PartialEq)]
-    struct Answer {
-        message: String,
+    pub struct Answer {
+        pub message: String,
     }
 
+    #[derive(Debug, Deserialize, JsonSchema)]
+    pub struct EchoInput {
+        pub text: String,
+    }
+
+    #[derive(Debug, Serialize)]
+    pub struct EchoOutput {
+        pub echoed: String,
+    }
+
+    pub struct EchoTool;
+
+    impl Tool for EchoTool {
+        type Input = EchoInput;
+        type Output = EchoOutput;
+
+        fn name(&self) -> &'static str {
+            "echo"
+        }
+
+        fn call(&self, input: Self::Input) -> Self::Output {
+            EchoOutput { echoed: input.text }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::test_support::*;
+    use super::*;
+    use std::cell::RefCell;
+
     #[derive(Default)]
     struct FakeModel {
         prompts: RefCell<Vec<String>>,
@@ -167,7 +788,277 @@ mod tests {
 
         let prompts = agent.model.prompts.borrow();
         assert_eq!(prompts.len(), 2);
-        assert!(prompts[1].contains("Validation error"));
+        assert!(prompts[1].contains("Validation errors"));
         assert!(prompts[1].contains("Return valid JSON only."));
     }
+
+    #[test]
+    fn reports_schema_violations_when_json_parses_but_fails_constraints() {
+        let model = FakeModel::with_responses(&["{\"message\":123}"]);
+        let agent: Agent<(), Answer, _> = Agent::new(model).with_max_retries(0);
+
+        let err = agent.run("Say hello", &()).unwrap_err();
+
+        match &err {
+            AgentError::Validation { attempts } => {
+                assert_eq!(attempts.len(), 1);
+                match &attempts[0].failure {
+                    AttemptFailure::Schema(violations) => {
+                        assert!(!violations.is_empty());
+                        assert!(violations[0].instance_pointer().starts_with('/'));
+                    }
+                    other => panic!("expected schema failure, got {other:?}"),
+                }
+            }
+            other => panic!("expected validation error, got {other:?}"),
+        }
+        assert_eq!(err.attempts().len(), 1);
+    }
+
+    #[test]
+    fn schema_violation_display_surfaces_schema_path() {
+        let violation = ValidationError {
+            msg: "123 is not of type \"string\"".to_string(),
+            instance_path: vec!["message".to_string()],
+            schema_path: vec!["properties".to_string(), "message".to_string(), "type".to_string()],
+        };
+
+        let rendered = violation.to_string();
+        assert!(rendered.contains("/message"));
+        assert!(rendered.contains("/properties/message/type"));
+    }
+
+    #[test]
+    fn tool_result_survives_a_subsequent_validation_retry() {
+        let model = FakeModel::with_responses(&[
+            "{\"method\":\"echo\",\"params\":{\"text\":\"secret-data-123\"}}",
+            "not-json",
+            "{\"message\":\"done\"}",
+        ]);
+        let agent: Agent<(), Answer, _> = Agent::new(model)
+            .with_tools(ToolRegistry::new().register(EchoTool))
+            .with_max_retries(1);
+
+        let output = agent.run("Say hello", &()).unwrap();
+        assert_eq!(output.message, "done");
+
+        let prompts = agent.model.prompts.borrow();
+        assert_eq!(prompts.len(), 3);
+        assert!(prompts[2].contains("secret-data-123"));
+    }
+
+    #[test]
+    fn dispatches_tool_call_then_returns_final_answer() {
+        let model = FakeModel::with_responses(&[
+            "{\"method\":\"echo\",\"params\":{\"text\":\"hi\"}}",
+            "{\"message\":\"done\"}",
+        ]);
+        let agent: Agent<(), Answer, _> =
+            Agent::new(model).with_tools(ToolRegistry::new().register(EchoTool));
+
+        let output = agent.run("Say hello", &()).unwrap();
+        assert_eq!(output.message, "done");
+
+        let prompts = agent.model.prompts.borrow();
+        assert_eq!(prompts.len(), 2);
+        assert!(prompts[1].contains("Tool `echo` returned"));
+        assert!(prompts[1].contains("\"echoed\":\"hi\""));
+    }
+
+    #[test]
+    fn tool_call_without_params_defaults_to_null() {
+        let call = ToolCall::from_value(&serde_json::json!({"method": "echo"})).unwrap();
+        assert_eq!(call.method, "echo");
+        assert_eq!(call.params, serde_json::Value::Null);
+    }
+
+    #[test]
+    fn unrecognized_tool_method_falls_through_to_validation() {
+        let model = FakeModel::with_responses(&["{\"method\":\"missing\",\"params\":{}}"]);
+        let agent: Agent<(), Answer, _> = Agent::new(model)
+            .with_tools(ToolRegistry::new().register(EchoTool))
+            .with_max_retries(0);
+
+        let err = agent.run("Say hello", &()).unwrap_err();
+        assert!(matches!(err, AgentError::Validation { .. }));
+    }
+
+    #[derive(Debug, Deserialize, JsonSchema, PartialEq)]
+    struct MethodShapedOutput {
+        method: String,
+        params: serde_json::Value,
+    }
+
+    #[test]
+    fn output_schema_with_method_field_is_not_misdetected_as_tool_call() {
+        let model = FakeModel::with_responses(&["{\"method\":\"summarize\",\"params\":{}}"]);
+        let agent: Agent<(), MethodShapedOutput, _> = Agent::new(model);
+
+        let output = agent.run("Summarize", &()).unwrap();
+        assert_eq!(output.method, "summarize");
+    }
+
+    #[test]
+    fn exceeding_max_tool_steps_is_reported() {
+        let model = FakeModel::with_responses(&["{\"method\":\"echo\",\"params\":{\"text\":\"hi\"}}"]);
+        let agent: Agent<(), Answer, _> = Agent::new(model)
+            .with_tools(ToolRegistry::new().register(EchoTool))
+            .with_max_tool_steps(0);
+
+        let err = agent.run("Say hello", &()).unwrap_err();
+        assert!(matches!(err, AgentError::ToolStepsExceeded { max_steps: 0 }));
+    }
+
+    #[test]
+    fn lenient_parsing_strips_markdown_fence_and_prose() {
+        let model = FakeModel::with_responses(&[
+            "Sure, here you go:\n```json\n{\"message\":\"hello\"}\n```\nLet me know if that helps!",
+        ]);
+        let agent: Agent<(), Answer, _> = Agent::new(model).with_lenient_parsing(true);
+
+        let output = agent.run("Say hello", &()).unwrap();
+        assert_eq!(output.message, "hello");
+    }
+
+    #[test]
+    fn lenient_parsing_off_still_fails_on_dirty_output() {
+        let model = FakeModel::with_responses(&["```json\n{\"message\":\"hello\"}\n```"]);
+        let agent: Agent<(), Answer, _> = Agent::new(model).with_max_retries(0);
+
+        assert!(agent.run("Say hello", &()).is_err());
+    }
+
+    #[test]
+    fn sanitizes_lone_surrogate_escape() {
+        let repaired = repair_json("{\"message\":\"bad \\uD800 surrogate\"}").unwrap();
+        assert!(serde_json::from_str::<serde_json::Value>(&repaired).is_ok());
+        assert!(repaired.contains('\u{FFFD}'));
+    }
+
+    #[test]
+    fn sanitize_preserves_escaped_backslash_followed_by_literal_u_text() {
+        let raw = "{\"path\":\"C:\\\\uD800abc\"}";
+        let sanitized = sanitize_lone_surrogates(raw);
+
+        assert_eq!(sanitized, raw);
+        let original: serde_json::Value = serde_json::from_str(raw).unwrap();
+        let roundtripped: serde_json::Value = serde_json::from_str(&sanitized).unwrap();
+        assert_eq!(original, roundtripped);
+    }
+
+    #[derive(Debug)]
+    struct TransportError;
+
+    impl Display for TransportError {
+        fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+            write!(f, "connection reset")
+        }
+    }
+
+    impl Error for TransportError {}
+
+    struct FailingModel;
+
+    impl ModelClient<()> for FailingModel {
+        fn complete(&self, _prompt: &str, _deps: &()) -> Result<String, Box<dyn Error + Send + Sync>> {
+            Err(Box::new(TransportError))
+        }
+    }
+
+    #[test]
+    fn model_error_preserves_source_and_trace() {
+        let agent: Agent<(), Answer, _> = Agent::new(FailingModel);
+
+        let err = agent.run("Say hello", &()).unwrap_err();
+
+        assert!(matches!(err, AgentError::Model { .. }));
+        assert_eq!(err.attempts().len(), 1);
+        assert_eq!(err.source().unwrap().to_string(), "connection reset");
+    }
+}
+
+#[cfg(all(test, feature = "async"))]
+mod async_tests {
+    use super::test_support::*;
+    use super::*;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct FakeAsyncModel {
+        prompts: Mutex<Vec<String>>,
+        responses: Mutex<Vec<String>>,
+    }
+
+    impl FakeAsyncModel {
+        fn with_responses(responses: &[&str]) -> Self {
+            Self {
+                prompts: Mutex::new(Vec::new()),
+                responses: Mutex::new(responses.iter().rev().map(|v| (*v).to_string()).collect()),
+            }
+        }
+    }
+
+    impl AsyncModelClient<()> for FakeAsyncModel {
+        fn complete<'a>(
+            &'a self,
+            prompt: String,
+            _deps: &'a (),
+        ) -> BoxFuture<'a, Result<String, Box<dyn Error + Send + Sync>>> {
+            self.prompts.lock().unwrap().push(prompt);
+            let response = self
+                .responses
+                .lock()
+                .unwrap()
+                .pop()
+                .unwrap_or_else(|| "{\"message\":\"fallback\"}".to_string());
+            Box::pin(std::future::ready(Ok(response)))
+        }
+    }
+
+    #[test]
+    fn returns_typed_output() {
+        let model = FakeAsyncModel::with_responses(&["{\"message\":\"hello\"}"]);
+        let agent: AsyncAgent<(), Answer, _> = AsyncAgent::new(model);
+
+        let output = block_on_ready(agent.run("Say hello", &())).unwrap();
+
+        assert_eq!(
+            output,
+            Answer {
+                message: "hello".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn retries_with_reflection_prompt_after_validation_error() {
+        let model = FakeAsyncModel::with_responses(&["not-json", "{\"message\":\"fixed\"}"]);
+        let agent: AsyncAgent<(), Answer, _> = AsyncAgent::new(model).with_max_retries(1);
+
+        let output = block_on_ready(agent.run("Need JSON", &())).unwrap();
+        assert_eq!(output.message, "fixed");
+
+        let prompts = agent.model.prompts.lock().unwrap();
+        assert_eq!(prompts.len(), 2);
+        assert!(prompts[1].contains("Validation errors"));
+        assert!(prompts[1].contains("Return valid JSON only."));
+    }
+
+    #[test]
+    fn dispatches_tool_call_then_returns_final_answer() {
+        let model = FakeAsyncModel::with_responses(&[
+            "{\"method\":\"echo\",\"params\":{\"text\":\"hi\"}}",
+            "{\"message\":\"done\"}",
+        ]);
+        let agent: AsyncAgent<(), Answer, _> =
+            AsyncAgent::new(model).with_tools(ToolRegistry::new().register(EchoTool));
+
+        let output = block_on_ready(agent.run("Say hello", &())).unwrap();
+        assert_eq!(output.message, "done");
+
+        let prompts = agent.model.prompts.lock().unwrap();
+        assert_eq!(prompts.len(), 2);
+        assert!(prompts[1].contains("Tool `echo` returned"));
+        assert!(prompts[1].contains("\"echoed\":\"hi\""));
+    }
 }